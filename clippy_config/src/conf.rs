@@ -0,0 +1,12 @@
+use crate::types::SelfImportStyle;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Conf {
+    /// Which root intra-crate imports `use_crate_prefix_for_self_imports` should enforce:
+    /// `"crate"` to require a `crate::` prefix (the default), or `"relative"` to require the
+    /// shorter `self::`/`super::` form instead.
+    #[serde(default)]
+    pub self_import_style: SelfImportStyle,
+}
@@ -0,0 +1,5 @@
+mod conf;
+mod types;
+
+pub use conf::Conf;
+pub use types::SelfImportStyle;
@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+/// Which root intra-crate imports `use_crate_prefix_for_self_imports` should enforce, set via
+/// the `self-import-style` clippy.toml option.
+#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelfImportStyle {
+    /// Prefer `crate::foo::bar` (the default).
+    #[default]
+    Crate,
+    /// Prefer `self::bar` / `super::bar` over `crate::foo::bar` where `foo` is an ancestor module.
+    Relative,
+}
@@ -1,14 +1,16 @@
+use clippy_config::{Conf, SelfImportStyle};
 use clippy_utils::diagnostics::span_lint_and_sugg;
 use clippy_utils::source::snippet_opt;
-use rustc_ast::{Item, ItemKind, ast};
-use rustc_data_structures::fx::FxHashSet;
 use rustc_errors::Applicability;
-use rustc_lint::{EarlyContext, EarlyLintPass, LintContext};
+use rustc_hir::def::Res;
+use rustc_hir::def_id::{LOCAL_CRATE, LocalDefId};
+use rustc_hir::definitions::DefPathData;
+use rustc_hir::{Item, ItemKind, UseKind, UsePath};
+use rustc_lint::{LateContext, LateLintPass};
 use rustc_session::impl_lint_pass;
-use rustc_span::FileName;
-use rustc_span::def_id::LOCAL_CRATE;
-use std::ffi::OsString;
-use std::path::Path;
+use rustc_span::edition::Edition;
+use rustc_span::symbol::kw;
+use rustc_span::{Span, Symbol};
 
 declare_clippy_lint! {
     /// ### What it does
@@ -29,65 +31,226 @@ declare_clippy_lint! {
     /// ```no_run
     /// use crate::foo::bar;
     /// ```
+    ///
+    /// Each branch of a grouped import is checked on its own, so only the crate-local branches
+    /// are rewritten:
+    /// ```no_run
+    /// use {foo::bar, other_crate::baz};
+    /// ```
+    /// Use instead:
+    /// ```no_run
+    /// use {crate::foo::bar, other_crate::baz};
+    /// ```
+    ///
+    /// A shared prefix is rewritten once, at the group itself, rather than on every branch:
+    /// ```no_run
+    /// use foo::{a, b};
+    /// ```
+    /// Use instead:
+    /// ```no_run
+    /// use crate::foo::{a, b};
+    /// ```
+    ///
+    /// With `self-import-style = "relative"` in `clippy.toml`, the lint instead enforces the
+    /// opposite style, flagging `crate::`-prefixed imports that reach into the current module's
+    /// own ancestry and suggesting the shorter `self::`/`super::` form.
     #[clippy::version = "1.84.0"]
     pub USE_CRATE_PREFIX_FOR_SELF_IMPORTS,
     style,
-    "checks that imports from the current crate use the `crate::` prefix"
+    "checks that imports from the current crate use a consistent `crate::` or `self::`/`super::` style"
 }
 
-impl_lint_pass!(UseCratePrefixForSelfImports => [USE_CRATE_PREFIX_FOR_SELF_IMPORTS]);
-
-#[derive(Default)]
 pub struct UseCratePrefixForSelfImports {
-    mod_set: FxHashSet<OsString>,
+    self_import_style: SelfImportStyle,
+    // Span of the most recently seen `ListStem` item whose shared prefix was just rewritten to
+    // `crate::`. Leaf `Single` items contained in that span (the rest of the same group) must not
+    // be suggested on individually, since the one suggestion on the stem already covers them.
+    grouped_prefix: Option<Span>,
 }
 
-impl EarlyLintPass for UseCratePrefixForSelfImports {
-    fn check_crate(&mut self, cx: &EarlyContext<'_>, _: &ast::Crate) {
-        let files = cx.sess().source_map().files();
+impl UseCratePrefixForSelfImports {
+    pub fn new(conf: &Conf) -> Self {
+        Self {
+            self_import_style: conf.self_import_style,
+            grouped_prefix: None,
+        }
+    }
+}
+
+impl_lint_pass!(UseCratePrefixForSelfImports => [USE_CRATE_PREFIX_FOR_SELF_IMPORTS]);
+
+impl<'tcx> LateLintPass<'tcx> for UseCratePrefixForSelfImports {
+    fn check_item(&mut self, cx: &LateContext<'tcx>, item: &Item<'tcx>) {
+        // `crate::` is only meaningful from edition 2018 onwards.
+        if cx.tcx.sess.edition() < Edition::Edition2018 {
+            return;
+        }
+
+        // Macro-generated `use` items have a meaningless suggestion span, and applying the fix
+        // could corrupt the macro that produced them.
+        if item.span.from_expansion() {
+            return;
+        }
+
+        let ItemKind::Use(path, use_kind) = item.kind else {
+            return;
+        };
+
+        // `use foo::{a, b};` lowers to one synthetic `ListStem` item carrying the shared prefix
+        // (`foo`) plus one `Single` item per leaf (`a`, `b`). Each leaf's path is the *full*
+        // resolved path (`foo::a`, `foo::b`), so rewriting a leaf on its own would either repeat
+        // the shared prefix (`use foo::{crate::a, ...}`, invalid) or, via that path's span, overlap
+        // with its siblings. The shared prefix is therefore only ever rewritten once, here, at the
+        // stem; leaves that fall inside the rewritten stem's span are skipped below.
+        if let UseKind::ListStem = use_kind {
+            self.grouped_prefix = None;
+            if self.self_import_style == SelfImportStyle::Crate && suggest_crate_prefix(cx, path) {
+                self.grouped_prefix = Some(item.span);
+            }
+            return;
+        }
+
+        if let Some(stem_span) = self.grouped_prefix
+            && stem_span.contains(item.span)
+        {
+            return;
+        }
+        self.grouped_prefix = None;
 
-        let Some(trim_to_src) = cx.sess().opts.working_dir.local_path() else {
+        let Some(first_segment) = path.segments.first() else {
             return;
         };
 
-        for file in files.iter() {
-            if let FileName::Real(name) = &file.name
-                && let Some(lp) = name.local_path()
-                && file.cnum == LOCAL_CRATE
-            {
-                let path = if lp.is_relative() {
-                    lp
-                } else if let Ok(relative) = lp.strip_prefix(trim_to_src) {
-                    relative
+        match self.self_import_style {
+            SelfImportStyle::Crate => {
+                suggest_crate_prefix(cx, path);
+            },
+            SelfImportStyle::Relative => {
+                if first_segment.ident.name != kw::Crate {
+                    return;
+                }
+
+                // `use crate as foo;` has no module segments between `crate` and the renamed
+                // item, so there's no ancestor path to rewrite to `self::`/`super::`. Without this
+                // guard `path.segments[1..path.segments.len() - 1]` below panics (`1..0`).
+                if path.segments.len() < 2 {
+                    return;
+                }
+
+                // `parent_module` returns a `LocalModDefId`, which `module_path` needs as a plain
+                // `LocalDefId`.
+                let current_mod = module_path(cx, cx.tcx.parent_module(item.hir_id()).to_local_def_id());
+                // Segments between `crate` and the imported item itself name the modules the
+                // import passes through.
+                let imported_mod = &path.segments[1..path.segments.len() - 1];
+
+                if imported_mod.len() > current_mod.len()
+                    || imported_mod
+                        .iter()
+                        .zip(&current_mod)
+                        .any(|(seg, module)| seg.ident.name != *module)
+                {
+                    // Not an ancestor of the current module: `crate::` is still the clearest way
+                    // to spell this import.
+                    return;
+                }
+
+                let levels_up = current_mod.len() - imported_mod.len();
+                let replacement = if levels_up == 0 {
+                    "self".to_owned()
                 } else {
-                    continue;
+                    vec!["super"; levels_up].join("::")
                 };
 
-                if let Some(root) = path.components().nth(1) {
-                    let root: &Path = root.as_ref();
-                    if let Some(mod_name) = root.file_stem() {
-                        self.mod_set.insert(mod_name.to_owned());
-                    };
-                }
-            }
+                let old_prefix: String = std::iter::once("crate".to_owned())
+                    .chain(imported_mod.iter().map(|seg| seg.ident.name.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("::");
+
+                // `path.span` is the resolved path alone (`crate::foo::bar`); `item.span` covers
+                // the whole statement (`use crate::foo::bar;`), which never starts with
+                // `old_prefix` and would make this `strip_prefix` always fail.
+                let Some(rest) = snippet_opt(cx, path.span).and_then(|s| s.strip_prefix(&old_prefix).map(str::to_owned))
+                else {
+                    return;
+                };
+
+                span_lint_and_sugg(
+                    cx,
+                    USE_CRATE_PREFIX_FOR_SELF_IMPORTS,
+                    path.span,
+                    "this import uses `crate::` to refer to an ancestor module",
+                    format!("use `{replacement}::` instead"),
+                    format!("{replacement}{rest}"),
+                    Applicability::MachineApplicable,
+                );
+            },
         }
     }
+}
 
-    fn check_item(&mut self, cx: &EarlyContext<'_>, item: &Item) {
-        if let ItemKind::Use(use_tree) = &item.kind {
-            if let Some(x) = use_tree.prefix.segments.first() {
-                if self.mod_set.contains(&OsString::from(x.ident.name.as_str())) {
-                    span_lint_and_sugg(
-                        cx,
-                        USE_CRATE_PREFIX_FOR_SELF_IMPORTS,
-                        use_tree.span,
-                        "this import is not clear",
-                        "prefix with `crate::`",
-                        format!("crate::{}", snippet_opt(cx, use_tree.span).unwrap()),
-                        Applicability::MachineApplicable,
-                    );
-                }
-            }
-        }
+/// Checks whether `path`'s first segment is an unambiguous local-crate item and, if so, suggests
+/// prefixing `path.span` with `crate::`. Returns whether a suggestion was emitted.
+fn suggest_crate_prefix(cx: &LateContext<'_>, path: &UsePath<'_>) -> bool {
+    let Some(first_segment) = path.segments.first() else {
+        return false;
+    };
+
+    // `crate::`, `self::` and `super::` imports are already unambiguous, and `$crate::` only ever
+    // shows up inside macros, where rewriting the span would be meaningless.
+    if matches!(
+        first_segment.ident.name,
+        kw::Crate | kw::SelfLower | kw::Super | kw::DollarCrate
+    ) {
+        return false;
     }
+
+    // A single item can resolve in more than one namespace (e.g. a tuple struct import resolves
+    // in both the type and value namespaces), so don't treat that as ambiguous on its own. Only
+    // bail out if the resolutions disagree on which crate they come from, which is the case we
+    // actually can't give a safe suggestion for.
+    let mut def_ids = path.res.iter().filter_map(|res| match res {
+        Res::Def(_, def_id) => Some(*def_id),
+        _ => None,
+    });
+
+    let Some(first_def_id) = def_ids.next() else {
+        return false;
+    };
+
+    if first_def_id.krate != LOCAL_CRATE || def_ids.any(|def_id| def_id.krate != LOCAL_CRATE) {
+        return false;
+    }
+
+    // The span of a desugared use path isn't guaranteed to map to clean source, so bail out
+    // rather than unwrap if we can't get a snippet for it.
+    let Some(snippet) = snippet_opt(cx, path.span) else {
+        return false;
+    };
+
+    span_lint_and_sugg(
+        cx,
+        USE_CRATE_PREFIX_FOR_SELF_IMPORTS,
+        path.span,
+        "this import is not clear",
+        "prefix with `crate::`",
+        format!("crate::{snippet}"),
+        Applicability::MachineApplicable,
+    );
+
+    true
+}
+
+/// The type-namespace names on `def_id`'s path, outermost first, ending with `def_id`'s own name
+/// and not including the crate root. For a module, this is the module's path from the crate root.
+fn module_path(cx: &LateContext<'_>, def_id: LocalDefId) -> Vec<Symbol> {
+    cx.tcx
+        .def_path(def_id.to_def_id())
+        .data
+        .iter()
+        .filter_map(|disambiguated| match disambiguated.data {
+            DefPathData::TypeNs(Some(sym)) => Some(sym),
+            _ => None,
+        })
+        .collect()
 }